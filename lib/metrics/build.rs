@@ -0,0 +1,6 @@
+extern crate prost_build;
+
+fn main() {
+    prost_build::compile_protos(&["proto/histogram.proto"], &["proto/"])
+        .expect("histogram.proto must compile");
+}
@@ -1,8 +1,10 @@
+use prost::{DecodeError, Message};
 use std::fmt;
 use std::marker::PhantomData;
-use std::{cmp, iter, slice};
+use std::{cmp, error, iter, slice, u64};
 
-use super::{Counter, FmtLabels, FmtMetric};
+use super::counter::MAX_PRECISE_COUNTER;
+use super::{Counter, FmtLabels, FmtMetric, HistogramProto};
 
 /// A series of latency values and counts.
 #[derive(Debug, Clone)]
@@ -45,6 +47,36 @@ pub enum Bucket {
 #[derive(Debug)]
 pub struct Bounds(pub &'static [Bucket]);
 
+/// An iterator over a histogram's `(upper_bound, count)` pairs.
+///
+/// The `+Inf` bucket is reported with an upper bound of `u64::MAX`.
+#[derive(Debug, Clone)]
+pub struct BucketIter<'a> {
+    bounds: &'a [Bucket],
+    buckets: &'a [Counter],
+    idx: usize,
+    include_empty: bool,
+}
+
+/// An iterator over a histogram's `(upper_bound, cumulative_count)` pairs, as
+/// reported by Prometheus histograms.
+#[derive(Debug, Clone)]
+pub struct CumulativeBucketIter<'a> {
+    inner: BucketIter<'a>,
+    total: u64,
+}
+
+/// Indicates that an encoded histogram could not be merged.
+#[derive(Debug)]
+pub enum MergeError {
+    /// The bytes were not a valid `HistogramProto`.
+    Decode(DecodeError),
+    /// The encoded histogram has a different number of buckets.
+    Buckets { expected: usize, actual: usize },
+    /// The encoded total does not match the sum of the encoded bucket counts.
+    TotalCount { expected: u64, actual: u64 },
+}
+
 /// Helper that lazily formats metric keys as {0}_{1}.
 struct Key<A: fmt::Display, B: fmt::Display>(A, B);
 
@@ -88,6 +120,154 @@ impl<V: Into<u64>> Histogram<V> {
         self.buckets[idx].incr();
         self.sum += value;
     }
+
+    /// Returns the number of buckets in this histogram.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Adds `other`'s bucket counts and sum to this histogram.
+    ///
+    /// # Panics
+    ///
+    /// If `other` does not have the same bounds as this histogram.
+    pub fn merge(&mut self, other: &Histogram<V>) {
+        assert_eq!(
+            self.bounds.0, other.bounds.0,
+            "histograms must have the same bounds"
+        );
+
+        for (bucket, &count) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket += count;
+        }
+        self.sum += other.sum;
+    }
+
+    /// Subtracts `other`'s bucket counts and sum from this histogram's.
+    ///
+    /// `other` is expected to be an earlier snapshot of this histogram. The
+    /// sum is then subtracted modulo the bound at which it wraps, so the
+    /// difference is correct even if the sum has wrapped since `other` was
+    /// recorded.
+    ///
+    /// If any of `other`'s bucket counts exceeds this histogram's, `other` is
+    /// not an earlier snapshot: those bucket counts are clamped at zero, and
+    /// so is the sum.
+    ///
+    /// # Panics
+    ///
+    /// If `other` does not have the same bounds as this histogram.
+    pub fn sub(&mut self, other: &Histogram<V>) {
+        assert_eq!(
+            self.bounds.0, other.bounds.0,
+            "histograms must have the same bounds"
+        );
+
+        let mut clamped = false;
+        for (bucket, &prior) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            clamped |= prior.value() > bucket.value();
+            *bucket = Counter::from(bucket.value().saturating_sub(prior.value()));
+        }
+
+        self.sum = if clamped {
+            Counter::default()
+        } else {
+            // Both sums are at most `MAX_PRECISE_COUNTER`, so this cannot
+            // overflow.
+            let sum = self.sum.value() + (MAX_PRECISE_COUNTER + 1) - other.sum.value();
+            Counter::from(sum % (MAX_PRECISE_COUNTER + 1))
+        };
+    }
+
+    /// Returns the observations recorded in `after` but not in `before`.
+    ///
+    /// # Panics
+    ///
+    /// If `before` and `after` do not have the same bounds.
+    pub fn delta(before: &Histogram<V>, after: &Histogram<V>) -> Histogram<V> {
+        let mut delta = Histogram {
+            bounds: after.bounds,
+            buckets: after.buckets.clone(),
+            sum: after.sum,
+            _p: PhantomData,
+        };
+        delta.sub(before);
+        delta
+    }
+
+    /// Encodes this histogram's bucket counts and sum.
+    pub fn to_proto(&self) -> HistogramProto {
+        let bucket_counts = self.buckets.iter().map(Counter::value).collect::<Vec<_>>();
+        HistogramProto {
+            total_count: bucket_counts.iter().sum(),
+            bucket_counts,
+            sum: self.sum.value(),
+        }
+    }
+
+    /// Decodes a `HistogramProto` and adds its bucket counts and sum to this
+    /// histogram.
+    ///
+    /// The encoded histogram must have been recorded with the same bounds as
+    /// this one; only the number of buckets can be validated. If an error is
+    /// returned, this histogram is not modified.
+    pub fn merge_from_proto(&mut self, bytes: &[u8]) -> Result<(), MergeError> {
+        let proto = HistogramProto::decode(bytes).map_err(MergeError::Decode)?;
+
+        if proto.bucket_counts.len() != self.buckets.len() {
+            return Err(MergeError::Buckets {
+                expected: self.buckets.len(),
+                actual: proto.bucket_counts.len(),
+            });
+        }
+
+        let total = proto
+            .bucket_counts
+            .iter()
+            .fold(0u64, |total, &count| total.wrapping_add(count));
+        if total != proto.total_count {
+            return Err(MergeError::TotalCount {
+                expected: total,
+                actual: proto.total_count,
+            });
+        }
+
+        for (bucket, &count) in self.buckets.iter_mut().zip(proto.bucket_counts.iter()) {
+            *bucket += count;
+        }
+        self.sum += proto.sum;
+
+        Ok(())
+    }
+
+    /// Iterates over the upper bound and count of each non-empty bucket, in
+    /// ascending order.
+    pub fn iter_buckets(&self) -> BucketIter {
+        BucketIter {
+            bounds: self.bounds.0,
+            buckets: &self.buckets,
+            idx: 0,
+            include_empty: false,
+        }
+    }
+
+    /// Iterates over the upper bound and count of every bucket, including
+    /// empty ones, in ascending order.
+    pub fn iter_all_buckets(&self) -> BucketIter {
+        BucketIter {
+            include_empty: true,
+            ..self.iter_buckets()
+        }
+    }
+
+    /// Iterates over the upper bound of every bucket along with the number of
+    /// observations less than or equal to it.
+    pub fn cumulative_iter_buckets(&self) -> CumulativeBucketIter {
+        CumulativeBucketIter {
+            inner: self.iter_all_buckets(),
+            total: 0,
+        }
+    }
 }
 
 #[cfg(any(test, feature = "test_util"))]
@@ -168,6 +348,14 @@ impl<V: Into<u64>> Histogram<V> {
     }
 }
 
+impl<V: Into<u64>, U: Into<V>> Extend<U> for Histogram<V> {
+    fn extend<I: IntoIterator<Item = U>>(&mut self, iter: I) {
+        for u in iter {
+            self.add(u);
+        }
+    }
+}
+
 impl<'a, V: Into<u64>> IntoIterator for &'a Histogram<V> {
     type Item = (&'a Bucket, &'a Counter);
     type IntoIter = iter::Zip<slice::Iter<'a, Bucket>, slice::Iter<'a, Counter>>;
@@ -209,6 +397,84 @@ impl<V: Into<u64>> FmtMetric for Histogram<V> {
     }
 }
 
+// ===== impl BucketIter =====
+
+impl<'a> Iterator for BucketIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.idx < self.buckets.len() {
+            let idx = self.idx;
+            self.idx += 1;
+
+            let count = self.buckets[idx].value();
+            if count == 0 && !self.include_empty {
+                continue;
+            }
+
+            let upper_bound = match self.bounds[idx] {
+                Bucket::Le(ceiling) => ceiling,
+                Bucket::Inf => u64::MAX,
+            };
+            return Some((upper_bound, count));
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.buckets.len() - self.idx;
+        if self.include_empty {
+            (remaining, Some(remaining))
+        } else {
+            (0, Some(remaining))
+        }
+    }
+}
+
+// ===== impl CumulativeBucketIter =====
+
+impl<'a> Iterator for CumulativeBucketIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (upper_bound, count) = self.inner.next()?;
+        self.total += count;
+        Some((upper_bound, self.total))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+// ===== impl MergeError =====
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::Decode(ref e) => write!(f, "invalid histogram encoding: {}", e),
+            MergeError::Buckets { expected, actual } => {
+                write!(f, "histogram has {} buckets; expected {}", actual, expected)
+            }
+            MergeError::TotalCount { expected, actual } => write!(
+                f,
+                "histogram total count is {}; expected {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for MergeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MergeError::Decode(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 // ===== impl Key =====
 
 impl<A: fmt::Display, B: fmt::Display> fmt::Display for Key<A, B> {
@@ -332,6 +598,224 @@ mod tests {
         Bucket::Inf,
     ]);
 
+    #[test]
+    fn bucket_count_matches_bounds() {
+        let hist = Histogram::<u64>::new(&BOUNDS);
+        assert_eq!(hist.bucket_count(), BOUNDS.0.len());
+    }
+
+    #[test]
+    fn merge_complementary_samples() {
+        let mut low = Histogram::<u64>::new(&BOUNDS);
+        let mut high = Histogram::<u64>::new(&BOUNDS);
+        let mut all = Histogram::<u64>::new(&BOUNDS);
+        for obs in &[5u64, 10, 25] {
+            low.add(*obs);
+            all.add(*obs);
+        }
+        for obs in &[250u64, 250, 2_000_000] {
+            high.add(*obs);
+            all.add(*obs);
+        }
+
+        low.merge(&high);
+        low.assert_bucket_exactly(10, 2)
+            .assert_bucket_exactly(30, 1)
+            .assert_bucket_exactly(300, 2)
+            .assert_bucket_exactly(u64::MAX, 1);
+        assert_eq!(low.buckets, all.buckets);
+        assert_eq!(low.sum.value(), 2_000_540);
+    }
+
+    #[test]
+    #[should_panic]
+    fn merge_requires_same_bounds() {
+        static OTHER_BOUNDS: &'static Bounds = &Bounds(&[Bucket::Le(10), Bucket::Inf]);
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.merge(&Histogram::<u64>::new(&OTHER_BOUNDS));
+    }
+
+    #[test]
+    fn iter_buckets_empty() {
+        let hist = Histogram::<u64>::new(&BOUNDS);
+        assert_eq!(hist.iter_buckets().count(), 0);
+        assert_eq!(hist.iter_all_buckets().count(), BOUNDS.0.len());
+        assert!(hist.cumulative_iter_buckets().all(|(_, count)| count == 0));
+    }
+
+    #[test]
+    fn iter_buckets_single_bucket() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.add(15u64);
+        hist.add(20u64);
+
+        let buckets = hist.iter_buckets().collect::<Vec<_>>();
+        assert_eq!(buckets, vec![(20, 2)]);
+
+        for (upper_bound, count) in hist.cumulative_iter_buckets() {
+            let expected = if upper_bound < 20 { 0 } else { 2 };
+            assert_eq!(count, expected, "upper_bound={};", upper_bound);
+        }
+    }
+
+    #[test]
+    fn iter_buckets_inf() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.add(2_000_000u64);
+
+        let buckets = hist.iter_buckets().collect::<Vec<_>>();
+        assert_eq!(buckets, vec![(u64::MAX, 1)]);
+    }
+
+    #[test]
+    fn iter_buckets_multi_bucket() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        for obs in &[5u64, 10, 25, 250, 250, 250] {
+            hist.add(*obs);
+        }
+
+        let buckets = hist.iter_buckets().collect::<Vec<_>>();
+        assert_eq!(buckets, vec![(10, 2), (30, 1), (300, 3)]);
+
+        let cumulative = hist.cumulative_iter_buckets().collect::<Vec<_>>();
+        assert_eq!(cumulative.len(), BOUNDS.0.len());
+        assert_eq!(cumulative[0], (10, 2));
+        assert_eq!(cumulative[1], (20, 2));
+        assert_eq!(cumulative[2], (30, 3));
+        assert_eq!(cumulative[11], (300, 6));
+        assert_eq!(cumulative.last(), Some(&(u64::MAX, 6)));
+    }
+
+    #[test]
+    fn sub_clamps_at_zero() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.add(5u64);
+
+        let mut other = Histogram::<u64>::new(&BOUNDS);
+        other.add(5u64);
+        other.add(5u64);
+        other.add(500u64);
+
+        hist.sub(&other);
+        assert_eq!(hist.iter_buckets().count(), 0);
+        assert_eq!(hist.sum.value(), 0);
+    }
+
+    #[test]
+    fn delta_sum_after_wrap() {
+        let mut before = Histogram::<u64>::new(&BOUNDS);
+        before.add(MAX_PRECISE_COUNTER - 1);
+
+        let mut after = before.clone();
+        after.add(5u64);
+        // The sum has wrapped past `MAX_PRECISE_COUNTER`.
+        assert_eq!(after.sum.value(), 3);
+
+        let delta = Histogram::delta(&before, &after);
+        assert_eq!(delta.sum.value(), 5);
+        delta.assert_bucket_exactly(5, 1).assert_gt_exactly(5, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sub_requires_same_bounds() {
+        static OTHER_BOUNDS: &'static Bounds = &Bounds(&[Bucket::Le(10), Bucket::Inf]);
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.sub(&Histogram::<u64>::new(&OTHER_BOUNDS));
+    }
+
+    fn encode(hist: &Histogram<u64>) -> Vec<u8> {
+        let proto = hist.to_proto();
+        let mut buf = Vec::with_capacity(proto.encoded_len());
+        proto.encode(&mut buf).expect("encoding must succeed");
+        buf
+    }
+
+    #[test]
+    fn proto_round_trip() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        for obs in &[5u64, 10, 25, 250, 250, 2_000_000] {
+            hist.add(*obs);
+        }
+
+        let proto = hist.to_proto();
+        assert_eq!(proto.bucket_counts.len(), BOUNDS.0.len());
+        assert_eq!(proto.total_count, 6);
+        assert_eq!(proto.sum, hist.sum.value());
+
+        let mut decoded = Histogram::<u64>::new(&BOUNDS);
+        decoded
+            .merge_from_proto(&encode(&hist))
+            .expect("merge must succeed");
+        assert_eq!(decoded.buckets, hist.buckets);
+        assert_eq!(decoded.sum, hist.sum);
+    }
+
+    #[test]
+    fn merge_from_proto_adds_counts() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        hist.add(5u64);
+        hist.add(250u64);
+
+        let mut remote = Histogram::<u64>::new(&BOUNDS);
+        remote.add(5u64);
+        remote.add(700u64);
+
+        hist.merge_from_proto(&encode(&remote))
+            .expect("merge must succeed");
+        hist.assert_bucket_exactly(10, 2)
+            .assert_bucket_exactly(300, 1)
+            .assert_bucket_exactly(700, 1);
+        assert_eq!(hist.sum.value(), 960);
+    }
+
+    #[test]
+    fn merge_from_proto_rejects_invalid_bytes() {
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        // A truncated varint.
+        match hist.merge_from_proto(&[0x80]) {
+            Err(MergeError::Decode(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn merge_from_proto_rejects_mismatched_buckets() {
+        static OTHER_BOUNDS: &'static Bounds = &Bounds(&[Bucket::Le(10), Bucket::Inf]);
+        let mut remote = Histogram::<u64>::new(&OTHER_BOUNDS);
+        remote.add(5u64);
+
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        match hist.merge_from_proto(&encode(&remote)) {
+            Err(MergeError::Buckets { expected, actual }) => {
+                assert_eq!(expected, BOUNDS.0.len());
+                assert_eq!(actual, 2);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(hist.buckets, Histogram::<u64>::new(&BOUNDS).buckets);
+    }
+
+    #[test]
+    fn merge_from_proto_rejects_inconsistent_total() {
+        let mut remote = Histogram::<u64>::new(&BOUNDS);
+        remote.add(5u64);
+        let mut proto = remote.to_proto();
+        proto.total_count = 2;
+        let mut bytes = Vec::with_capacity(proto.encoded_len());
+        proto.encode(&mut bytes).expect("encoding must succeed");
+
+        let mut hist = Histogram::<u64>::new(&BOUNDS);
+        match hist.merge_from_proto(&bytes) {
+            Err(MergeError::TotalCount { expected, actual }) => {
+                assert_eq!(expected, 1);
+                assert_eq!(actual, 2);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(hist.buckets, Histogram::<u64>::new(&BOUNDS).buckets);
+    }
+
     quickcheck! {
         fn bucket_incremented(obs: u64) -> bool {
             let mut hist = Histogram::<u64>::new(&BOUNDS);
@@ -397,5 +881,41 @@ mod tests {
             }
             true
         }
+
+        fn merge_equals_adding_all(left: Vec<u64>, right: Vec<u64>) -> bool {
+            let mut merged = Histogram::<u64>::new(&BOUNDS);
+            let mut other = Histogram::<u64>::new(&BOUNDS);
+            let mut expected = Histogram::<u64>::new(&BOUNDS);
+            for obs in &left {
+                merged.add(*obs);
+                expected.add(*obs);
+            }
+            for obs in &right {
+                other.add(*obs);
+                expected.add(*obs);
+            }
+
+            merged.merge(&other);
+            merged.buckets == expected.buckets && merged.sum == expected.sum
+        }
+
+        fn delta_is_inverse_of_add(before: Vec<u64>, since: Vec<u64>) -> bool {
+            let mut prior = Histogram::<u64>::new(&BOUNDS);
+            let mut expected = Histogram::<u64>::new(&BOUNDS);
+            let mut after = Histogram::<u64>::new(&BOUNDS);
+            for obs in &before {
+                prior.add(*obs);
+                after.add(*obs);
+            }
+            for obs in &since {
+                expected.add(*obs);
+                after.add(*obs);
+            }
+
+            let delta = Histogram::delta(&prior, &after);
+            after.sub(&prior);
+            delta.buckets == expected.buckets && after.buckets == expected.buckets
+                && delta.sum == expected.sum && after.sum == expected.sum
+        }
     }
 }
@@ -1,3 +1,4 @@
+use std::iter::FromIterator;
 use std::time::Duration;
 
 use super::histogram::{Bounds, Bucket, Histogram};
@@ -34,6 +35,45 @@ pub const BOUNDS: &Bounds = &Bounds(&[
     Bucket::Inf,
 ]);
 
+/// Latency buckets, in milliseconds, for operations expected to take on the
+/// order of seconds.
+pub const SECOND_BOUNDS: &Bounds = &Bounds(&[
+    Bucket::Le(100),
+    Bucket::Le(250),
+    Bucket::Le(500),
+    Bucket::Le(1_000),
+    Bucket::Le(2_000),
+    Bucket::Le(3_000),
+    Bucket::Le(4_000),
+    Bucket::Le(5_000),
+    Bucket::Le(10_000),
+    Bucket::Le(15_000),
+    Bucket::Le(20_000),
+    Bucket::Le(30_000),
+    Bucket::Le(60_000),
+    // A final upper bound.
+    Bucket::Inf,
+]);
+
+/// Latency buckets, in milliseconds, for operations expected to take on the
+/// order of minutes.
+pub const MINUTE_BOUNDS: &Bounds = &Bounds(&[
+    Bucket::Le(1_000),
+    Bucket::Le(5_000),
+    Bucket::Le(10_000),
+    Bucket::Le(30_000),
+    Bucket::Le(60_000),
+    Bucket::Le(120_000),
+    Bucket::Le(180_000),
+    Bucket::Le(300_000),
+    Bucket::Le(600_000),
+    Bucket::Le(900_000),
+    Bucket::Le(1_800_000),
+    Bucket::Le(3_600_000),
+    // A final upper bound.
+    Bucket::Inf,
+]);
+
 /// A duration in milliseconds.
 #[derive(Debug, Default, Clone)]
 pub struct Ms(Duration);
@@ -58,3 +98,172 @@ impl Default for Histogram<Ms> {
         Histogram::new(BOUNDS)
     }
 }
+
+impl Histogram<Ms> {
+    /// Builds a histogram with the given `bounds` from a series of durations.
+    pub fn from_iter_with_bounds<I>(iter: I, bounds: &'static Bounds) -> Self
+    where
+        I: IntoIterator<Item = Duration>,
+    {
+        let mut histo = Histogram::new(bounds);
+        histo.extend(iter);
+        histo
+    }
+
+    /// Computes the exact `quantile` of `samples` by rank order, interpolating
+    /// linearly between the two nearest ranks.
+    ///
+    /// Unlike a histogram's buckets, this is not limited by bucket width, so it
+    /// is useful as a reference value in tests.
+    ///
+    /// # Panics
+    ///
+    /// If `samples` is empty or `quantile` is not in `[0.0, 1.0]`.
+    pub fn actual_percentile(samples: &[u64], quantile: f64) -> f64 {
+        assert!(!samples.is_empty(), "samples must not be empty");
+        assert!(
+            quantile >= 0.0 && quantile <= 1.0,
+            "quantile must be in [0.0, 1.0]; quantile={}",
+            quantile
+        );
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let rank = quantile * (sorted.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        let (lo, hi) = (sorted[lower] as f64, sorted[upper] as f64);
+        lo + (hi - lo) * (rank - lower as f64)
+    }
+}
+
+impl FromIterator<Duration> for Histogram<Ms> {
+    fn from_iter<I: IntoIterator<Item = Duration>>(iter: I) -> Self {
+        Histogram::from_iter_with_bounds(iter, BOUNDS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_percentile(samples: &[u64], quantile: f64, expected: f64) {
+        let actual = Histogram::actual_percentile(samples, quantile);
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "quantile={}; actual={}; expected={};",
+            quantile,
+            actual,
+            expected,
+        );
+    }
+
+    #[test]
+    fn actual_percentile_single_sample() {
+        assert_percentile(&[42], 0.0, 42.0);
+        assert_percentile(&[42], 0.5, 42.0);
+        assert_percentile(&[42], 1.0, 42.0);
+    }
+
+    #[test]
+    fn actual_percentile_extremes() {
+        let samples = [30, 10, 50, 20, 40];
+        assert_percentile(&samples, 0.0, 10.0);
+        assert_percentile(&samples, 1.0, 50.0);
+    }
+
+    #[test]
+    fn actual_percentile_exact_rank() {
+        // Quantiles that fall on a rank of unsorted samples are exact.
+        let samples = [30, 10, 50, 20, 40];
+        assert_percentile(&samples, 0.5, 30.0);
+        assert_percentile(&samples, 0.25, 20.0);
+    }
+
+    #[test]
+    fn actual_percentile_interpolates() {
+        // The median of an even number of samples falls between the middle two.
+        assert_percentile(&[10, 20, 30, 40], 0.5, 25.0);
+        let samples = (1..=100).collect::<Vec<u64>>();
+        assert_percentile(&samples, 0.95, 95.05);
+    }
+
+    #[test]
+    #[should_panic]
+    fn actual_percentile_requires_samples() {
+        Histogram::actual_percentile(&[], 0.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn actual_percentile_requires_valid_quantile() {
+        Histogram::actual_percentile(&[10, 20], 1.5);
+    }
+
+    fn assert_valid_bounds(bounds: &'static Bounds) {
+        // `Histogram::new` asserts that the bounds are strictly increasing.
+        let _ = Histogram::<Ms>::new(bounds);
+        assert_eq!(bounds.0.last(), Some(&Bucket::Inf));
+    }
+
+    #[test]
+    fn default_bounds_are_valid() {
+        assert_valid_bounds(BOUNDS);
+    }
+
+    #[test]
+    fn second_bounds_are_valid() {
+        assert_valid_bounds(SECOND_BOUNDS);
+        let histo =
+            Histogram::from_iter_with_bounds(vec![Duration::from_millis(1_500)], SECOND_BOUNDS);
+        histo
+            .assert_bucket_exactly(2_000, 1)
+            .assert_lt_exactly(2_000, 0);
+    }
+
+    #[test]
+    fn minute_bounds_are_valid() {
+        assert_valid_bounds(MINUTE_BOUNDS);
+        let histo = Histogram::from_iter_with_bounds(vec![Duration::from_secs(90)], MINUTE_BOUNDS);
+        histo
+            .assert_bucket_exactly(120_000, 1)
+            .assert_lt_exactly(120_000, 0);
+    }
+
+    #[test]
+    fn from_empty_iter() {
+        let histo: Histogram<Ms> = Vec::<Duration>::new().into_iter().collect();
+        histo.assert_bucket_exactly(1, 0).assert_gt_exactly(1, 0);
+    }
+
+    #[test]
+    fn from_single_element_iter() {
+        let histo: Histogram<Ms> = Some(Duration::from_millis(30)).into_iter().collect();
+        histo
+            .assert_bucket_exactly(30, 1)
+            .assert_lt_exactly(30, 0)
+            .assert_gt_exactly(30, 0);
+    }
+
+    #[test]
+    fn from_iter_out_of_bounds() {
+        let histo: Histogram<Ms> = vec![Duration::from_secs(60), Duration::from_secs(3_600)]
+            .into_iter()
+            .collect();
+        // Values above the largest bound are recorded in the `+Inf` bucket.
+        histo
+            .assert_bucket_exactly(50_001, 2)
+            .assert_lt_exactly(50_001, 0);
+    }
+
+    #[test]
+    fn extend_adds_to_existing() {
+        let mut histo =
+            Histogram::<Ms>::from_iter_with_bounds(vec![Duration::from_millis(3)], BOUNDS);
+        histo.extend(vec![Duration::from_millis(3), Duration::from_millis(200)]);
+        histo
+            .assert_bucket_exactly(3, 2)
+            .assert_bucket_exactly(200, 1);
+    }
+}
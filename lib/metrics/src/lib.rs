@@ -1,5 +1,6 @@
 //! Utilties for exposing metrics to Prometheus.
 
+extern crate bytes;
 extern crate deflate;
 extern crate futures;
 extern crate http;
@@ -7,6 +8,9 @@ extern crate hyper;
 extern crate indexmap;
 #[macro_use]
 extern crate log;
+extern crate prost;
+#[macro_use]
+extern crate prost_derive;
 #[cfg(test)]
 #[macro_use]
 extern crate quickcheck;
@@ -19,10 +23,15 @@ mod prom;
 mod scopes;
 mod serve;
 
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/linkerd2.metrics.rs"));
+}
+
 pub use self::counter::Counter;
 pub use self::gauge::Gauge;
-pub use self::histogram::Histogram;
+pub use self::histogram::{BucketIter, CumulativeBucketIter, Histogram, MergeError};
 pub use self::prom::{FmtLabels, FmtMetric, FmtMetrics, Metric};
+pub use self::proto::Histogram as HistogramProto;
 pub use self::scopes::Scopes;
 pub use self::serve::Serve;
 
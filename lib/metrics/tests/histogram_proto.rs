@@ -0,0 +1,55 @@
+extern crate linkerd2_metrics;
+extern crate prost;
+
+use linkerd2_metrics::{latency, Histogram};
+use prost::Message;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+fn histogram(latencies_ms: &[u64]) -> Histogram<latency::Ms> {
+    latencies_ms
+        .iter()
+        .map(|&ms| Duration::from_millis(ms))
+        .collect()
+}
+
+/// Simulates two processes that each record latencies and send their encoded
+/// histogram over a loopback connection to a third that aggregates them.
+#[test]
+fn histograms_exchanged_over_loopback() {
+    let remotes = vec![vec![1u64, 15, 25, 25], vec![250u64, 2_500, 25, 60_000]];
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("must bind");
+    let addr = listener.local_addr().expect("must have a local address");
+
+    let senders = remotes
+        .clone()
+        .into_iter()
+        .map(|latencies| {
+            thread::spawn(move || {
+                let proto = histogram(&latencies).to_proto();
+                let mut buf = Vec::with_capacity(proto.encoded_len());
+                proto.encode(&mut buf).expect("encoding must succeed");
+
+                let mut stream = TcpStream::connect(addr).expect("must connect");
+                stream.write_all(&buf).expect("must write");
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut merged = Histogram::<latency::Ms>::default();
+    for _ in 0..remotes.len() {
+        let (mut stream, _) = listener.accept().expect("must accept");
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).expect("must read");
+        merged.merge_from_proto(&buf).expect("merge must succeed");
+    }
+    for sender in senders {
+        sender.join().expect("sender must not panic");
+    }
+
+    let all = remotes.iter().flatten().cloned().collect::<Vec<_>>();
+    assert_eq!(merged.to_proto(), histogram(&all).to_proto());
+}